@@ -21,10 +21,26 @@ pub struct AWSRuntimeAPIClient {
 pub struct RequestId(String);
 pub struct Invocation<T> {
     request_id: RequestId,
+    context: InvocationContext,
     payload: T,
 }
 
+/// The invocation context AWS conventionally passes to a handler as its second
+/// argument. Populated from the `Lambda-Runtime-*` headers on the next-unit-of
+/// -work response and the function's environment.
+pub struct InvocationContext {
+    request_id: String,
+    deadline_ms: Option<String>,
+    invoked_function_arn: Option<String>,
+    trace_id: Option<String>,
+}
+
 const REQUEST_ID_HEADER: &str = "Lambda-Runtime-Aws-Request-Id";
+const DEADLINE_HEADER: &str = "Lambda-Runtime-Deadline-Ms";
+const INVOKED_FUNCTION_ARN_HEADER: &str = "Lambda-Runtime-Invoked-Function-Arn";
+const TRACE_ID_HEADER: &str = "Lambda-Runtime-Trace-Id";
+const FUNCTION_NAME: &str = "AWS_LAMBDA_FUNCTION_NAME";
+const FUNCTION_MEMORY: &str = "AWS_LAMBDA_FUNCTION_MEMORY_SIZE";
 const LAMBDA_RUNTIME_API: &str = "AWS_LAMBDA_RUNTIME_API";
 
 impl RequestId {
@@ -45,6 +61,50 @@ impl RequestId {
     }
 }
 
+impl InvocationContext {
+    /// Assemble the context from the headers on a next-unit-of-work response.
+    fn from_request<T>(request_id: &RequestId, result: &Response<T>) -> InvocationContext {
+        let header = |name: &str| {
+            result
+                .headers()
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(|value| value.to_string())
+        };
+
+        InvocationContext {
+            request_id: request_id.0.clone(),
+            deadline_ms: header(DEADLINE_HEADER),
+            invoked_function_arn: header(INVOKED_FUNCTION_ARN_HEADER),
+            trace_id: header(TRACE_ID_HEADER),
+        }
+    }
+
+    /// A synthetic context for local runs outside the Lambda environment.
+    pub fn local() -> InvocationContext {
+        InvocationContext {
+            request_id: "00000000-0000-0000-0000-000000000000".to_string(),
+            deadline_ms: None,
+            invoked_function_arn: None,
+            trace_id: None,
+        }
+    }
+
+    /// Render the context into the field names a JS handler expects on its
+    /// `context` argument, merging in the function name and memory limit from
+    /// the environment.
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "awsRequestId": self.request_id,
+            "invokedFunctionArn": self.invoked_function_arn,
+            "deadlineMs": self.deadline_ms,
+            "_xAmznTraceId": self.trace_id,
+            "functionName": std::env::var(FUNCTION_NAME).ok(),
+            "memoryLimitInMB": std::env::var(FUNCTION_MEMORY).ok(),
+        })
+    }
+}
+
 impl AWSRuntimeAPIClient {
     pub fn from_environment() -> Result<AWSRuntimeAPIClient> {
         Ok(AWSRuntimeAPIClient {
@@ -80,12 +140,14 @@ impl AWSRuntimeAPIClient {
         );
 
         let request_id = RequestId::from_request(&result)?;
+        let context = InvocationContext::from_request(&request_id, &result);
         let body_data = hyper::body::to_bytes(result.into_body()).await?;
         let result = serde_json::from_slice::<T>(body_data.borrow())?;
 
         Result::Ok(Invocation {
             payload: result,
             request_id,
+            context,
         })
     }
 
@@ -155,21 +217,67 @@ pub struct LambdaRuntime<Input, Output> {
     task_handler: Box<dyn Handler<Input, Output>>,
 }
 
-#[derive(Serialize, Debug)]
+#[derive(Debug)]
 pub enum HandlerError {
     ClientError,
     ServerError(String),
+    JsException {
+        message: String,
+        line: usize,
+        column: usize,
+        stack: String,
+    },
+}
+
+/// The error payload shape expected by the `/runtime/invocation/{id}/error`
+/// endpoint of the Lambda Runtime API.
+#[derive(Serialize, Debug)]
+pub struct LambdaErrorResponse {
+    #[serde(rename = "errorMessage")]
+    pub error_message: String,
+    #[serde(rename = "errorType")]
+    pub error_type: String,
+    #[serde(rename = "stackTrace")]
+    pub stack_trace: Vec<String>,
 }
 
+impl HandlerError {
+    /// Render this error into the `errorMessage`/`errorType`/`stackTrace`
+    /// payload the Runtime API reports back to the invoker.
+    pub fn to_response(&self) -> LambdaErrorResponse {
+        match self {
+            HandlerError::ClientError => LambdaErrorResponse {
+                error_message: "Client error".to_string(),
+                error_type: "ClientError".to_string(),
+                stack_trace: Vec::new(),
+            },
+            HandlerError::ServerError(message) => LambdaErrorResponse {
+                error_message: message.clone(),
+                error_type: "ServerError".to_string(),
+                stack_trace: Vec::new(),
+            },
+            HandlerError::JsException {
+                message, stack, ..
+            } => LambdaErrorResponse {
+                error_message: message.clone(),
+                error_type: "Error".to_string(),
+                stack_trace: stack.lines().map(str::to_string).collect(),
+            },
+        }
+    }
+}
+
+impl std::error::Error for HandlerError {}
+
 impl Display for HandlerError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         f.write_str(&format!("{:?}", self))
     }
 }
 
-#[async_trait]
+#[async_trait(?Send)]
 pub trait Handler<Input, Output> {
-    async fn handle(&self, input: &Input) -> Result<Output>;
+    async fn handle(&self, input: &Input, context: &InvocationContext) -> Result<Output>;
 }
 
 impl<Input, Output> LambdaRuntime<Input, Output>
@@ -191,19 +299,20 @@ where
         loop {
             let work = self.client.get_next_unit_of_work::<Input>().await?;
 
-            let task_result = self.task_handler.handle(&work.payload).await;
+            let task_result = self
+                .task_handler
+                .handle(&work.payload, &work.context)
+                .await;
 
             match task_result {
                 Ok(result) => self.client.report_success(work.request_id, result).await?,
                 Err(err) => {
-                    let serializable_error = match err.downcast_ref::<HandlerError>() {
-                        Some(HandlerError::ClientError) => HandlerError::ClientError,
-                        _ => HandlerError::ServerError(format!("{}", err)),
+                    let response = match err.downcast_ref::<HandlerError>() {
+                        Some(handler_error) => handler_error.to_response(),
+                        None => HandlerError::ServerError(format!("{}", err)).to_response(),
                     };
 
-                    self.client
-                        .report_error(work.request_id, serializable_error)
-                        .await?
+                    self.client.report_error(work.request_id, response).await?
                 }
             }
         }
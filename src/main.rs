@@ -1,27 +1,36 @@
-use crate::aws::Handler;
+use crate::aws::{Handler, HandlerError, InvocationContext};
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
 use rusty_v8 as v8;
 use rusty_v8::{Isolate, Local, ToLocal, Value};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
+use std::path::PathBuf;
 
 use tokio;
 
 mod aws;
+mod source_map;
 mod tasks;
 
+use crate::source_map::SourceMap;
+use crate::tasks::Extensions;
+
 pub fn script_origin<'a>(
     s: &mut impl v8::ToLocal<'a>,
     resource_name: v8::Local<'a, v8::String>,
+    source_map_url: &str,
+    is_module: bool,
 ) -> v8::ScriptOrigin<'a> {
     let resource_line_offset = v8::Integer::new(s, 0);
     let resource_column_offset = v8::Integer::new(s, 0);
     let resource_is_shared_cross_origin = v8::Boolean::new(s, false);
     let script_id = v8::Integer::new(s, 123);
-    let source_map_url = v8::String::new(s, "source_map_url").unwrap();
+    let source_map_url = v8::String::new(s, source_map_url).unwrap();
     let resource_is_opaque = v8::Boolean::new(s, true);
     let is_wasm = v8::Boolean::new(s, false);
-    let is_module = v8::Boolean::new(s, false);
+    let is_module = v8::Boolean::new(s, is_module);
     v8::ScriptOrigin::new(
         resource_name.into(),
         resource_line_offset,
@@ -37,10 +46,12 @@ pub fn script_origin<'a>(
 
 fn execute_global_function<'s>(
     scope: &mut impl v8::ToLocal<'s>,
+    platform: &JSPlatform,
     context: v8::Local<v8::Context>,
     name: &str,
-    parameter: v8::Local<v8::Value>,
-) -> Result<String> {
+    parameters: &[v8::Local<v8::Value>],
+    source_map: Option<&SourceMap>,
+) -> Result<serde_json::Value> {
     let accessor = v8::String::new(scope, name).unwrap();
 
     let reference = match context.global(scope).get(scope, context, accessor.into()) {
@@ -53,18 +64,129 @@ fn execute_global_function<'s>(
     let undefined = v8::undefined(scope).into();
 
     let mut try_catch = v8::TryCatch::new(scope);
-    let ts = try_catch.enter();
-    let result = function.call(scope, context, undefined, &vec![parameter]);
+    let _ts = try_catch.enter();
+    let result = function.call(scope, context, undefined, parameters);
+
+    let value = match result {
+        Some(value) => value,
+        None => {
+            return Err(build_js_exception(scope, context, &mut try_catch, source_map).into())
+        }
+    };
+
+    // Modern Lambda handlers are `async function`s that return a Promise. Drain
+    // the microtask queue (and pump the platform message loop for any deferred
+    // work) until the promise settles, then decode whichever side it took.
+    if value.is_promise() {
+        let promise: v8::Local<v8::Promise> = value.try_into()?;
+
+        while promise.state() == v8::PromiseState::Pending {
+            scope.isolate().run_microtasks();
+            platform.pump_message_loop(scope.isolate());
+        }
+
+        let settled = promise.result(scope);
+        match promise.state() {
+            v8::PromiseState::Fulfilled => decode(scope, context, settled),
+            v8::PromiseState::Rejected => {
+                Err(build_rejection_exception(scope, context, settled, source_map).into())
+            }
+            v8::PromiseState::Pending => unreachable!("promise left the pending loop unresolved"),
+        }
+    } else {
+        decode(scope, context, value)
+    }
+}
 
-    let p: v8::Local<v8::Promise> = result.into();
-    p.then()
+/// Inspect an entered [`v8::TryCatch`] and assemble a structured
+/// [`HandlerError::JsException`] carrying the exception message, the offending
+/// source position, and the full JS stack trace so the runtime can report a
+/// faithful `errorMessage`/`errorType`/`stackTrace` payload.
+fn build_js_exception<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+    try_catch: &mut v8::TryCatch,
+    source_map: Option<&SourceMap>,
+) -> HandlerError {
+    let exception_message = try_catch
+        .exception()
+        .map(|exception| exception.to_rust_string_lossy(scope))
+        .unwrap_or_else(|| "Uncaught (unknown) exception".to_string());
+
+    let (mut line, mut column) = (0, 0);
+    if let Some(message) = try_catch.message() {
+        line = message.get_line_number(context).unwrap_or(0);
+        // V8 reports a 0-based start column; positions here are 1-based, like
+        // the `line:column` frames of a stack trace.
+        column = message.get_start_column() + 1;
+    }
 
-    match result {
-        Some(value) => {
-            let result_string: v8::Local<v8::String> = value.try_into()?;
-            Ok(result_string.to_rust_string_lossy(scope))
+    // Prefer the full JS stack trace; fall back to the bare message when the
+    // engine did not capture one (e.g. a thrown non-Error value).
+    let mut stack = try_catch
+        .stack_trace(scope, context)
+        .map(|stack| stack.to_rust_string_lossy(scope))
+        .filter(|stack| !stack.is_empty())
+        .unwrap_or_else(|| exception_message.clone());
+
+    // When the script carried a sourceMappingURL, translate the reported
+    // position and stack frames back to their original (pre-transpile) source.
+    if let Some(source_map) = source_map {
+        if let Some((_, original_line, original_column)) = source_map.remap_position(line, column) {
+            line = original_line;
+            column = original_column;
         }
-        None => bail!("Encountered exception"),
+        stack = source_map.remap_stack(&stack);
+    }
+
+    HandlerError::JsException {
+        message: exception_message,
+        line,
+        column,
+        stack,
+    }
+}
+
+/// Build a structured [`HandlerError::JsException`] from a promise rejection
+/// value so async failures report like synchronous ones. A thrown `Error`
+/// keeps its `message`/`stack` as non-enumerable properties, so read them
+/// directly rather than decoding the object (whose `get_own_property_names`
+/// would come back empty).
+fn build_rejection_exception<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+    reason: v8::Local<v8::Value>,
+    source_map: Option<&SourceMap>,
+) -> HandlerError {
+    let object: Option<v8::Local<v8::Object>> = reason.try_into().ok();
+
+    let message = object
+        .and_then(|object| {
+            let key = v8::String::new(scope, "message")?;
+            object.get(scope, context, key.into())
+        })
+        .filter(|value| !value.is_undefined() && !value.is_null())
+        .map(|value| value.to_rust_string_lossy(scope))
+        .unwrap_or_else(|| reason.to_rust_string_lossy(scope));
+
+    let mut stack = object
+        .and_then(|object| {
+            let key = v8::String::new(scope, "stack")?;
+            object.get(scope, context, key.into())
+        })
+        .filter(|value| !value.is_undefined() && !value.is_null())
+        .map(|value| value.to_rust_string_lossy(scope))
+        .unwrap_or_else(|| message.clone());
+
+    if let Some(source_map) = source_map {
+        stack = source_map.remap_stack(&stack);
+    }
+
+    HandlerError::JsException {
+        message,
+        line: 0,
+        column: 0,
+        stack,
     }
 }
 
@@ -89,13 +211,33 @@ impl JSPlatform {
     }
 }
 
+/// A prepared isolate whose top-level script has already been compiled and run
+/// once, with the resulting context kept alive through a [`v8::Global`] handle
+/// so warm invocations skip recompilation entirely. `baseline_globals` records
+/// the global property names present immediately after initialization; any
+/// globals a handler adds are stripped back to this set before the next request
+/// so per-request state does not leak while the compiled context is reused.
+struct WarmContext {
+    isolate: v8::OwnedIsolate,
+    context: v8::Global<v8::Context>,
+    baseline_globals: HashSet<String>,
+}
+
 struct JSHandler {
     platform: JSPlatform,
+    entry_point: String,
     function: String,
+    source_map_url: String,
+    extensions: Extensions,
+    function_name: String,
+    hard_isolation: bool,
+    module: bool,
+    source_map: Option<SourceMap>,
+    warm: RefCell<Option<WarmContext>>,
 }
 
 impl JSHandler {
-    fn new(entry_point: &str) -> Result<JSHandler> {
+    fn new(entry_point: &str, extensions: Extensions) -> Result<JSHandler> {
         let platform = JSPlatform::new();
         platform.initialize_platform();
         v8::V8::initialize();
@@ -103,29 +245,199 @@ impl JSHandler {
         let function = std::fs::read_to_string(entry_point)
             .with_context(|| format!("Unable to load handler entrypoint {}", entry_point))?;
 
-        Ok(JSHandler { platform, function })
+        let source_map_url = extract_source_map_url(&function).unwrap_or_default();
+        let source_map = load_source_map(entry_point, &source_map_url);
+
+        Ok(JSHandler {
+            platform,
+            entry_point: entry_point.to_string(),
+            function,
+            source_map_url,
+            extensions,
+            function_name: "handler".to_string(),
+            hard_isolation: false,
+            module: false,
+            source_map,
+            warm: RefCell::new(None),
+        })
     }
-}
 
-trait Transfer {
-    fn transfer<'sc>(&self, cs: &mut impl ToLocal<'sc>) -> Result<v8::Local<'sc, v8::Value>>;
-}
+    /// Compile the entrypoint as an ES module rather than a classic script,
+    /// enabling `import`/`export` and filesystem import resolution.
+    fn with_module(mut self, module: bool) -> JSHandler {
+        self.module = module;
+        self
+    }
+
+    /// Override the entrypoint function name looked up on the handler module.
+    /// Defaults to `handler`.
+    fn with_function_name(mut self, function_name: &str) -> JSHandler {
+        self.function_name = function_name.to_string();
+        self
+    }
+
+    /// Opt into recreating the isolate on every invocation. Slower, but gives
+    /// handlers that mutate global state hard isolation between requests.
+    fn with_hard_isolation(mut self, hard_isolation: bool) -> JSHandler {
+        self.hard_isolation = hard_isolation;
+        self
+    }
+
+    /// Install the native extensions and compile+run the top-level script,
+    /// defining the module globals on `context`.
+    fn initialize_context<'s>(
+        &self,
+        scope: &mut impl v8::ToLocal<'s>,
+        context: v8::Local<v8::Context>,
+    ) -> Result<()> {
+        self.extensions.install(scope, context);
+
+        if self.module {
+            return self.initialize_module_context(scope, context);
+        }
+
+        let script_source = v8::String::new(scope, &self.function).unwrap();
+        let resource_name = v8::String::new(scope, &self.entry_point).unwrap();
+        let origin = script_origin(scope, resource_name, &self.source_map_url, false);
+
+        let mut script =
+            v8::Script::compile(scope, context, script_source, Some(&origin)).unwrap();
+        script.run(scope, context);
+        Ok(())
+    }
+
+    /// Compile the entrypoint as an ES module, resolve and instantiate its
+    /// imports, evaluate it, then lift the handler export from the module
+    /// namespace onto the global object so the shared call path can find it.
+    fn initialize_module_context<'s>(
+        &self,
+        scope: &mut impl v8::ToLocal<'s>,
+        context: v8::Local<v8::Context>,
+    ) -> Result<()> {
+        let entry_path = std::fs::canonicalize(&self.entry_point)
+            .map(|path| path.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| self.entry_point.clone());
+
+        let base_dir = std::path::Path::new(&entry_path)
+            .parent()
+            .map(|parent| parent.to_path_buf())
+            .unwrap_or_default();
+
+        MODULE_MAP.with(|map| {
+            let mut map = map.borrow_mut();
+            map.base_dir = base_dir;
+            map.modules.clear();
+            map.paths.clear();
+        });
+
+        let module = match compile_module(scope, context, &entry_path, &self.function) {
+            Some(module) => module,
+            None => {
+                return Err(HandlerError::ServerError(format!(
+                    "Unable to compile entrypoint module {}",
+                    entry_path
+                ))
+                .into())
+            }
+        };
+
+        // Surface a failed specifier resolution or a throwing module top-level
+        // through the same structured exception path as classic scripts,
+        // instead of swallowing it and only failing later with the opaque
+        // "Reference handler was not a function".
+        let mut try_catch = v8::TryCatch::new(scope);
+        let _ts = try_catch.enter();
 
-impl Transfer for serde_json::Value {
-    fn transfer<'sc>(&self, sc: &mut impl ToLocal<'sc>) -> Result<Local<'sc, Value>> {
-        Ok(match self {
-            serde_json::Value::String(str) => {
-                v8::Local::from(v8::String::new(sc, str).unwrap()) // TODO: Handle error
+        if module.instantiate_module(context, resolve_module) != Some(true) {
+            return Err(build_js_exception(scope, context, &mut try_catch, self.source_map.as_ref()).into());
+        }
+
+        if module.evaluate(scope, context).is_none()
+            || module.get_status() == v8::ModuleStatus::Errored
+        {
+            return Err(build_js_exception(scope, context, &mut try_catch, self.source_map.as_ref()).into());
+        }
+
+        // The handler lives on the module namespace rather than the global
+        // object; copy it across under the configured name so `invoke` can
+        // resolve it the same way it does for classic scripts.
+        let namespace = module.get_module_namespace();
+        if let Ok(namespace) = TryInto::<v8::Local<v8::Object>>::try_into(namespace) {
+            let key = v8::String::new(scope, &self.function_name).unwrap();
+            if let Some(handler) = namespace.get(scope, context, key.into()) {
+                context.global(scope).set(context, key.into(), handler);
             }
-            serde_json::Value::Object(_m) => v8::Local::from(v8::Object::new(sc)),
-            _ => v8::Local::from(v8::undefined(sc)),
+        }
+
+        Ok(())
+    }
+
+    /// Transfer the event and context arguments in, call the entrypoint, and
+    /// decode the result. Shared by the warm and hard-isolation paths.
+    fn invoke<'s>(
+        &self,
+        scope: &mut impl v8::ToLocal<'s>,
+        context: v8::Local<v8::Context>,
+        input: &serde_json::Value,
+        invocation: &InvocationContext,
+    ) -> Result<serde_json::Value> {
+        let input_v8 = encode(scope, context, input)?;
+        let context_v8 = encode(scope, context, &invocation.to_json())?;
+
+        let result = execute_global_function(
+            scope,
+            &self.platform,
+            context,
+            &self.function_name,
+            &[input_v8, context_v8],
+            self.source_map.as_ref(),
+        )?;
+
+        self.platform.pump_message_loop(scope.isolate());
+
+        Ok(result)
+    }
+
+    /// Build a fresh isolate, run the top-level script once, and persist the
+    /// context (plus its baseline global property set) so subsequent
+    /// invocations reuse the compiled context instead of recompiling.
+    fn create_warm_context(&self) -> Result<WarmContext> {
+        let mut isolate_creation_params = v8::Isolate::create_params();
+        isolate_creation_params.set_array_buffer_allocator(v8::new_default_allocator());
+
+        let mut isolate = v8::Isolate::new(isolate_creation_params);
+
+        let (context, baseline_globals) = {
+            let mut hs = v8::HandleScope::new(&mut isolate);
+            let scope = hs.enter();
+
+            let context = v8::Context::new(scope);
+            let mut cs = v8::ContextScope::new(scope, context);
+            let scope = cs.enter();
+
+            self.initialize_context(scope, context)?;
+
+            let baseline_globals = global_property_names(scope, context);
+
+            let mut global = v8::Global::new();
+            global.set(scope, context);
+            (global, baseline_globals)
+        };
+
+        Ok(WarmContext {
+            isolate,
+            context,
+            baseline_globals,
         })
     }
-}
 
-#[async_trait]
-impl Handler<serde_json::Value, serde_json::Value> for JSHandler {
-    async fn handle(&self, input: &serde_json::Value) -> Result<serde_json::Value> {
+    /// The hard-isolation path: recompile and rerun the script in a brand-new
+    /// isolate per request.
+    fn handle_isolated(
+        &self,
+        input: &serde_json::Value,
+        invocation: &InvocationContext,
+    ) -> Result<serde_json::Value> {
         let mut isolate_creation_params = v8::Isolate::create_params();
         isolate_creation_params.set_array_buffer_allocator(v8::new_default_allocator());
 
@@ -138,18 +450,333 @@ impl Handler<serde_json::Value, serde_json::Value> for JSHandler {
         let mut cs = v8::ContextScope::new(scope, context);
         let scope = cs.enter();
 
-        let script_source = v8::String::new(scope, &self.function).unwrap();
+        self.initialize_context(scope, context)?;
+        self.invoke(scope, context, input, invocation)
+    }
+}
 
-        let mut script = v8::Script::compile(scope, context, script_source, None).unwrap();
-        script.run(scope, context);
+/// Collect the own property names currently defined on `context`'s global
+/// object as Rust strings.
+fn global_property_names<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+) -> HashSet<String> {
+    let global = context.global(scope);
+    let mut names = HashSet::new();
+    if let Some(property_names) = global.get_own_property_names(scope) {
+        for index in 0..property_names.length() {
+            let key = v8::Integer::new(scope, index as i32);
+            if let Some(name) = property_names.get(scope, context, key.into()) {
+                names.insert(name.to_rust_string_lossy(scope));
+            }
+        }
+    }
+    names
+}
 
-        let input_v8 = input.transfer(scope).unwrap(); // TODO: Better error handling
-        let result = execute_global_function(scope, context, "a", input_v8)?;
+/// Delete any global added since the context was compiled, restoring it to the
+/// `baseline` set captured right after initialization. Lets the warm path reuse
+/// the compiled context without leaking one request's globals into the next.
+fn reset_global_state<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+    baseline: &HashSet<String>,
+) {
+    let global = context.global(scope);
+    for name in global_property_names(scope, context) {
+        if baseline.contains(&name) {
+            continue;
+        }
+        if let Some(key) = v8::String::new(scope, &name) {
+            global.delete(context, key.into());
+        }
+    }
+}
 
-        self.platform.pump_message_loop(scope.isolate());
+/// Pick up a trailing `//# sourceMappingURL=` annotation from a compiled
+/// script. The URL is both threaded into the script's [`v8::ScriptOrigin`] and
+/// used to load the source map so reported stack frames can be remapped to
+/// their original positions (see [`load_source_map`]).
+fn extract_source_map_url(source: &str) -> Option<String> {
+    source.lines().rev().find_map(|line| {
+        let line = line.trim();
+        line.strip_prefix("//# sourceMappingURL=")
+            .or_else(|| line.strip_prefix("//@ sourceMappingURL="))
+            .map(|url| url.trim().to_string())
+    })
+}
 
-        Ok(serde_json::Value::String(result))
+/// Load and parse the source map referenced by `url`, relative to the
+/// `entry_point`. Handles both an inline `data:` URI (base64 or plain) and a
+/// filesystem path. Returns `None` when there is no usable map, so reporting
+/// silently falls back to the transpiled positions.
+fn load_source_map(entry_point: &str, url: &str) -> Option<SourceMap> {
+    if url.is_empty() {
+        return None;
     }
+
+    let json = if let Some(rest) = url.strip_prefix("data:") {
+        let comma = rest.find(',')?;
+        let (meta, data) = rest.split_at(comma);
+        let data = &data[1..];
+        if meta.contains("base64") {
+            String::from_utf8(source_map::decode_base64(data)?).ok()?
+        } else {
+            data.to_string()
+        }
+    } else {
+        let path = url.strip_prefix("file://").unwrap_or(url);
+        let base = std::path::Path::new(entry_point)
+            .parent()
+            .unwrap_or_else(|| std::path::Path::new("."));
+        std::fs::read_to_string(base.join(path)).ok()?
+    };
+
+    SourceMap::parse(&json)
+}
+
+/// The set of modules instantiated for the current entrypoint, keyed by their
+/// resolved (canonical) path. Populated as specifiers are resolved so repeated
+/// imports dedupe and import cycles terminate. Kept in thread-local storage
+/// because V8's `ResolveModuleCallback` is a bare `fn` that cannot capture
+/// state — the same approach Deno takes with its `ModuleMap`.
+#[derive(Default)]
+struct ModuleMap {
+    base_dir: PathBuf,
+    modules: HashMap<String, v8::Global<v8::Module>>,
+    /// Reverse index from a module's identity hash to its resolved path, so a
+    /// referrer can be mapped back to its own directory when resolving the
+    /// specifiers it imports.
+    paths: HashMap<i32, String>,
+}
+
+thread_local! {
+    static MODULE_MAP: RefCell<ModuleMap> = RefCell::new(ModuleMap::default());
+}
+
+/// Resolve an import `specifier` relative to `base_dir` (the directory of the
+/// module that issued the import) into a canonical path string.
+fn resolve_specifier(base_dir: &std::path::Path, specifier: &str) -> String {
+    let joined = base_dir.join(specifier);
+    std::fs::canonicalize(&joined)
+        .map(|path| path.to_string_lossy().into_owned())
+        .unwrap_or_else(|_| joined.to_string_lossy().into_owned())
+}
+
+/// Read, compile and register the module at `path`. The compiled module is
+/// inserted into the [`ModuleMap`] before it is returned so that cyclic imports
+/// referring back to it resolve to the same instance.
+fn compile_module<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+    path: &str,
+    source: &str,
+) -> Option<v8::Local<'s, v8::Module>> {
+    let source_text = v8::String::new(scope, source)?;
+    let resource_name = v8::String::new(scope, path)?;
+    let origin = script_origin(scope, resource_name, "", true);
+
+    let mut source = v8::script_compiler::Source::new(source_text, &origin);
+    let module = v8::script_compiler::compile_module(scope, &mut source)?;
+
+    let identity_hash = module.get_identity_hash();
+
+    let mut global = v8::Global::new();
+    global.set(scope, module);
+    MODULE_MAP.with(|map| {
+        let mut map = map.borrow_mut();
+        map.modules.insert(path.to_string(), global);
+        map.paths.insert(identity_hash, path.to_string());
+    });
+
+    Some(module)
+}
+
+/// V8 resolve callback: load a referenced specifier from the filesystem
+/// relative to the *referrer* module's own directory (so transitive relative
+/// imports resolve correctly), reusing the already-compiled module when the
+/// specifier has been seen before.
+fn resolve_module<'a>(
+    context: v8::Local<'a, v8::Context>,
+    specifier: v8::Local<'a, v8::String>,
+    referrer: v8::Local<'a, v8::Module>,
+) -> Option<v8::Local<'a, v8::Module>> {
+    let mut cs = v8::CallbackScope::new(context);
+    let scope = cs.enter();
+
+    let specifier = specifier.to_rust_string_lossy(scope);
+
+    // Resolve against the referrer's directory; fall back to the entrypoint's
+    // base directory when the referrer is not in the map.
+    let base_dir = MODULE_MAP.with(|map| {
+        let map = map.borrow();
+        map.paths
+            .get(&referrer.get_identity_hash())
+            .and_then(|path| std::path::Path::new(path).parent().map(PathBuf::from))
+            .unwrap_or_else(|| map.base_dir.clone())
+    });
+
+    let path = resolve_specifier(&base_dir, &specifier);
+
+    if let Some(module) = MODULE_MAP.with(|map| {
+        map.borrow()
+            .modules
+            .get(&path)
+            .and_then(|global| global.get(scope))
+    }) {
+        return Some(module);
+    }
+
+    let source = std::fs::read_to_string(&path).ok()?;
+    compile_module(scope, context, &path, &source)
+}
+
+/// Recursively encode a [`serde_json::Value`] into the matching V8 value, so
+/// that an arbitrary JSON-shaped input can be handed to a handler unchanged.
+pub(crate) fn encode<'sc>(
+    sc: &mut impl ToLocal<'sc>,
+    context: v8::Local<v8::Context>,
+    value: &serde_json::Value,
+) -> Result<Local<'sc, Value>> {
+    Ok(match value {
+        serde_json::Value::Null => v8::Local::from(v8::null(sc)),
+        serde_json::Value::Bool(b) => v8::Local::from(v8::Boolean::new(sc, *b)),
+        serde_json::Value::Number(number) => match number.as_i64() {
+            // Only take the integer fast-path when the value actually fits in
+            // an i32; anything wider (timestamps, byte counts, large ids) would
+            // wrap, so fall back to a double-precision Number.
+            Some(i) if i >= i64::from(i32::MIN) && i <= i64::from(i32::MAX) => {
+                v8::Local::from(v8::Integer::new(sc, i as i32))
+            }
+            _ => v8::Local::from(v8::Number::new(sc, number.as_f64().unwrap_or(0.0))),
+        },
+        serde_json::Value::String(str) => {
+            v8::Local::from(v8::String::new(sc, str).context("Unable to allocate V8 string")?)
+        }
+        serde_json::Value::Array(items) => {
+            let array = v8::Array::new(sc, items.len() as i32);
+            for (index, item) in items.iter().enumerate() {
+                let key = v8::Integer::new(sc, index as i32);
+                let item = encode(sc, context, item)?;
+                array.set(context, key.into(), item);
+            }
+            v8::Local::from(array)
+        }
+        serde_json::Value::Object(map) => {
+            let object = v8::Object::new(sc);
+            for (key, item) in map {
+                let key = v8::String::new(sc, key).context("Unable to allocate V8 string")?;
+                let item = encode(sc, context, item)?;
+                object.set(context, key.into(), item);
+            }
+            v8::Local::from(object)
+        }
+    })
+}
+
+/// Recursively decode a V8 value back into a [`serde_json::Value`] by probing
+/// its runtime type, so any JSON-representable handler result round-trips into
+/// the Lambda response.
+pub(crate) fn decode<'sc>(
+    scope: &mut impl ToLocal<'sc>,
+    context: v8::Local<v8::Context>,
+    value: v8::Local<v8::Value>,
+) -> Result<serde_json::Value> {
+    if value.is_null() || value.is_undefined() {
+        Ok(serde_json::Value::Null)
+    } else if value.is_boolean() {
+        Ok(serde_json::Value::Bool(value.boolean_value(scope)))
+    } else if value.is_number() {
+        let number = value.number_value(context).unwrap_or(0.0);
+        // Preserve the integer/float distinction so a handler returning `5`
+        // round-trips as a serde integer rather than `5.0`.
+        if number.fract() == 0.0
+            && number.is_finite()
+            && number >= i64::MIN as f64
+            && number <= i64::MAX as f64
+        {
+            Ok(serde_json::json!(number as i64))
+        } else {
+            Ok(serde_json::json!(number))
+        }
+    } else if value.is_string() {
+        let string: v8::Local<v8::String> = value.try_into()?;
+        Ok(serde_json::Value::String(string.to_rust_string_lossy(scope)))
+    } else if value.is_array() {
+        let array: v8::Local<v8::Array> = value.try_into()?;
+        let mut items = Vec::with_capacity(array.length() as usize);
+        for index in 0..array.length() {
+            let key = v8::Integer::new(scope, index as i32);
+            let item = array
+                .get(scope, context, key.into())
+                .context("Missing array element during decode")?;
+            items.push(decode(scope, context, item)?);
+        }
+        Ok(serde_json::Value::Array(items))
+    } else if value.is_object() {
+        let object: v8::Local<v8::Object> = value.try_into()?;
+        let mut map = serde_json::Map::new();
+        let names = object
+            .get_own_property_names(scope)
+            .context("Unable to read object property names")?;
+        for index in 0..names.length() {
+            let key_index = v8::Integer::new(scope, index as i32);
+            let key = names
+                .get(scope, context, key_index.into())
+                .context("Missing property name during decode")?;
+            let value = object
+                .get(scope, context, key)
+                .context("Missing property value during decode")?;
+            let key_string: v8::Local<v8::String> = key.try_into()?;
+            map.insert(key_string.to_rust_string_lossy(scope), decode(scope, context, value)?);
+        }
+        Ok(serde_json::Value::Object(map))
+    } else {
+        Ok(serde_json::Value::Null)
+    }
+}
+
+#[async_trait(?Send)]
+impl Handler<serde_json::Value, serde_json::Value> for JSHandler {
+    async fn handle(
+        &self,
+        input: &serde_json::Value,
+        invocation: &InvocationContext,
+    ) -> Result<serde_json::Value> {
+        // Handlers needing hard isolation between requests get a throwaway
+        // isolate; everyone else reuses the context compiled once on the first
+        // invocation, with per-request global state cleared in between.
+        if self.hard_isolation {
+            return self.handle_isolated(input, invocation);
+        }
+
+        let mut warm = self.warm.borrow_mut();
+        if warm.is_none() {
+            *warm = Some(self.create_warm_context()?);
+        }
+        let warm = warm.as_mut().unwrap();
+
+        let mut hs = v8::HandleScope::new(&mut warm.isolate);
+        let scope = hs.enter();
+
+        let context = warm.context.get(scope).unwrap();
+        let mut cs = v8::ContextScope::new(scope, context);
+        let scope = cs.enter();
+
+        // Reuse the already-compiled context, but strip any globals a previous
+        // invocation left behind so state does not leak between requests.
+        reset_global_state(scope, context, &warm.baseline_globals);
+
+        self.invoke(scope, context, input, invocation)
+    }
+}
+
+/// Read a boolean toggle from the environment, treating `1`/`true` (any case)
+/// as enabled and everything else (including an unset variable) as disabled.
+fn env_flag(name: &str) -> bool {
+    std::env::var(name)
+        .map(|value| value == "1" || value.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
 }
 
 #[tokio::main(max_threads = 1)]
@@ -164,10 +791,19 @@ async fn main() -> Result<()> {
     //     .await
     //     .context("Aborting event loop due to API Client execution error")?)
 
-    let handler = JSHandler::new("test_code.js")?;
+    let hard_isolation = env_flag("HANDLER_HARD_ISOLATION");
+    let module = env_flag("HANDLER_MODULE");
+
+    let mut handler = JSHandler::new("test_code.js", Extensions::standard())?
+        .with_module(module)
+        .with_hard_isolation(hard_isolation);
+
+    if let Ok(function_name) = std::env::var("HANDLER_FUNCTION_NAME") {
+        handler = handler.with_function_name(&function_name);
+    }
 
     let input = serde_json::Value::String("input".to_string());
-    let result = handler.handle(&input).await?;
+    let result = handler.handle(&input, &InvocationContext::local()).await?;
 
     println!("{}", result);
 
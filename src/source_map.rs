@@ -0,0 +1,201 @@
+//! A minimal Source Map v3 consumer: enough to translate a generated
+//! line/column — and the `file:line:column` frames in a V8 stack trace — back
+//! to the original positions recorded in a `sourceMappingURL`, so reported
+//! exceptions point at the handler author's source rather than the transpiled
+//! output.
+
+use serde::Deserialize;
+
+const BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64_digit(c: u8) -> Option<i64> {
+    BASE64.iter().position(|&b| b == c).map(|p| p as i64)
+}
+
+/// Decode a standard (RFC 4648) base64 payload, as carried by a
+/// `data:...;base64,` source-map URI. Padding is optional.
+pub fn decode_base64(input: &str) -> Option<Vec<u8>> {
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    let mut out = Vec::new();
+    for c in input.bytes() {
+        if c == b'=' || c == b'\n' || c == b'\r' {
+            continue;
+        }
+        let value = base64_digit(c)? as u32;
+        bits = (bits << 6) | value;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            out.push((bits >> bit_count) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Pull the next VLQ-encoded integer off `chars`, advancing the iterator.
+fn decode_vlq(chars: &mut std::str::Chars) -> Option<i64> {
+    let mut result: i64 = 0;
+    let mut shift = 0;
+    loop {
+        let digit = base64_digit(chars.next()? as u8)?;
+        let continuation = digit & 32;
+        result += (digit & 31) << shift;
+        if continuation == 0 {
+            let negative = result & 1;
+            result >>= 1;
+            return Some(if negative != 0 { -result } else { result });
+        }
+        shift += 5;
+    }
+}
+
+#[derive(Deserialize)]
+struct RawSourceMap {
+    #[serde(default)]
+    sources: Vec<String>,
+    mappings: String,
+}
+
+/// One decoded mapping for a generated line: a generated column and the
+/// original position it points at. All positions here are 0-based, as the
+/// source-map format stores them.
+struct Segment {
+    generated_column: u32,
+    source_index: usize,
+    original_line: u32,
+    original_column: u32,
+}
+
+/// A parsed source map, indexed by generated line for position lookups.
+pub struct SourceMap {
+    sources: Vec<String>,
+    lines: Vec<Vec<Segment>>,
+}
+
+impl SourceMap {
+    /// Parse a source map from its JSON text, decoding the VLQ `mappings`
+    /// stream into per-line segments. Returns `None` if the JSON is malformed.
+    pub fn parse(json: &str) -> Option<SourceMap> {
+        let raw: RawSourceMap = serde_json::from_str(json).ok()?;
+
+        let mut lines = Vec::new();
+        let mut source_index: i64 = 0;
+        let mut original_line: i64 = 0;
+        let mut original_column: i64 = 0;
+
+        for group in raw.mappings.split(';') {
+            let mut segments = Vec::new();
+            let mut generated_column: i64 = 0;
+
+            for segment in group.split(',') {
+                if segment.is_empty() {
+                    continue;
+                }
+
+                let mut chars = segment.chars();
+                generated_column += decode_vlq(&mut chars)?;
+
+                // A one-field segment carries only a generated column and maps
+                // nowhere; skip it. Otherwise the remaining deltas describe the
+                // original position.
+                if let Some(source_delta) = decode_vlq(&mut chars) {
+                    source_index += source_delta;
+                    original_line += decode_vlq(&mut chars)?;
+                    original_column += decode_vlq(&mut chars)?;
+                    // An optional name index may follow; it is not used here.
+                    let _ = decode_vlq(&mut chars);
+
+                    segments.push(Segment {
+                        generated_column: generated_column.max(0) as u32,
+                        source_index: source_index.max(0) as usize,
+                        original_line: original_line.max(0) as u32,
+                        original_column: original_column.max(0) as u32,
+                    });
+                }
+            }
+
+            segments.sort_by_key(|segment| segment.generated_column);
+            lines.push(segments);
+        }
+
+        Some(SourceMap {
+            sources: raw.sources,
+            lines,
+        })
+    }
+
+    /// Translate a 1-based generated `(line, column)` to the 1-based original
+    /// `(source, line, column)`, picking the last segment at or before the
+    /// column, or the line's first segment when none precedes it. Returns
+    /// `None` when the line carries no mapping.
+    pub fn remap_position(&self, line: usize, column: usize) -> Option<(String, usize, usize)> {
+        if line == 0 {
+            return None;
+        }
+
+        let segments = self.lines.get(line - 1)?;
+        let target = column.saturating_sub(1) as u32;
+
+        let mut best: Option<&Segment> = None;
+        for segment in segments {
+            if segment.generated_column <= target {
+                best = Some(segment);
+            } else {
+                break;
+            }
+        }
+
+        let segment = best.or_else(|| segments.first())?;
+        let source = self
+            .sources
+            .get(segment.source_index)
+            .cloned()
+            .unwrap_or_default();
+
+        Some((
+            source,
+            (segment.original_line + 1) as usize,
+            (segment.original_column + 1) as usize,
+        ))
+    }
+
+    /// Rewrite the `:line:column` suffix of every frame in a V8 stack trace to
+    /// its original position, leaving frames we cannot map untouched.
+    pub fn remap_stack(&self, stack: &str) -> String {
+        stack
+            .lines()
+            .map(|frame| self.remap_frame(frame))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn remap_frame(&self, frame: &str) -> String {
+        let closing = if frame.ends_with(')') { ")" } else { "" };
+        let trimmed = frame.trim_end_matches(')');
+
+        let column_sep = match trimmed.rfind(':') {
+            Some(index) => index,
+            None => return frame.to_string(),
+        };
+        let (head, column_str) = trimmed.split_at(column_sep);
+        let column_str = &column_str[1..];
+
+        let line_sep = match head.rfind(':') {
+            Some(index) => index,
+            None => return frame.to_string(),
+        };
+        let (location, line_str) = head.split_at(line_sep);
+        let line_str = &line_str[1..];
+
+        match (line_str.parse::<usize>(), column_str.parse::<usize>()) {
+            (Ok(line), Ok(column)) => match self.remap_position(line, column) {
+                Some((_, original_line, original_column)) => {
+                    format!("{}:{}:{}{}", location, original_line, original_column, closing)
+                }
+                None => frame.to_string(),
+            },
+            _ => frame.to_string(),
+        }
+    }
+}
@@ -1,16 +1,155 @@
+use crate::{decode, encode};
 use rusty_v8 as v8;
-use rusty_v8::{Context, Isolate, Local, OwnedIsolate};
 
-struct Tasks {
-    state: u64,
+/// A native binding exposed to handler code: a bare `fn` with the V8
+/// [`FunctionCallback`](v8::FunctionCallback) shape. Arguments are decoded with
+/// the shared [`decode`] transfer and return values encoded with [`encode`], so
+/// host ops speak in `serde_json::Value` rather than raw V8 handles.
+pub type NativeFunction =
+    fn(v8::FunctionCallbackScope, v8::FunctionCallbackArguments, v8::ReturnValue);
+
+/// A single entry in the extension registry. Either a function installed
+/// directly onto the global object (`fetch`) or a namespace object whose
+/// methods are themselves native functions (`console.log`/`console.error`).
+enum Binding {
+    Global(&'static str, NativeFunction),
+    Namespace(&'static str, Vec<(&'static str, NativeFunction)>),
+}
+
+/// The set of native ops installed onto a context before the handler script
+/// runs. This mirrors how Deno wires its builtin bindings through a function
+/// table, giving handler authors a way to perform I/O rather than executing in
+/// a sealed sandbox.
+pub struct Extensions {
+    bindings: Vec<Binding>,
+}
+
+impl Extensions {
+    /// An empty registry. Build it up with [`global`](Extensions::global) and
+    /// [`namespace`](Extensions::namespace).
+    pub fn new() -> Extensions {
+        Extensions {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// The builtin bindings shipped with the runtime: `console.log`,
+    /// `console.error` and a host `fetch`.
+    pub fn standard() -> Extensions {
+        Extensions::new()
+            .namespace(
+                "console",
+                vec![
+                    ("log", console_log as NativeFunction),
+                    ("error", console_error),
+                ],
+            )
+            .global("fetch", host_fetch)
+    }
+
+    /// Register a function onto the global object under `name`.
+    pub fn global(mut self, name: &'static str, callback: NativeFunction) -> Extensions {
+        self.bindings.push(Binding::Global(name, callback));
+        self
+    }
+
+    /// Register a namespace object (e.g. `console`) whose `methods` become
+    /// native functions on that object.
+    pub fn namespace(
+        mut self,
+        name: &'static str,
+        methods: Vec<(&'static str, NativeFunction)>,
+    ) -> Extensions {
+        self.bindings.push(Binding::Namespace(name, methods));
+        self
+    }
+
+    /// Install every registered binding onto `context`'s global object.
+    pub fn install<'s>(&self, scope: &mut impl v8::ToLocal<'s>, context: v8::Local<v8::Context>) {
+        let global = context.global(scope);
+
+        for binding in &self.bindings {
+            match binding {
+                Binding::Global(name, callback) => {
+                    let function = v8::Function::new(scope, context, *callback).unwrap();
+                    let key = v8::String::new(scope, name).unwrap();
+                    global.set(context, key.into(), function.into());
+                }
+                Binding::Namespace(name, methods) => {
+                    let object = v8::Object::new(scope);
+                    for (method, callback) in methods {
+                        let function = v8::Function::new(scope, context, *callback).unwrap();
+                        let key = v8::String::new(scope, method).unwrap();
+                        object.set(context, key.into(), function.into());
+                    }
+                    let key = v8::String::new(scope, name).unwrap();
+                    global.set(context, key.into(), object.into());
+                }
+            }
+        }
+    }
+}
+
+/// Render the call arguments as a single space-joined line, decoding each via
+/// the shared transfer. Strings are emitted bare (as CloudWatch would show
+/// them); everything else is rendered as its JSON encoding.
+fn format_arguments<'s>(
+    scope: &mut impl v8::ToLocal<'s>,
+    context: v8::Local<v8::Context>,
+    args: &v8::FunctionCallbackArguments,
+) -> String {
+    let mut parts = Vec::with_capacity(args.length() as usize);
+    for index in 0..args.length() {
+        let part = match decode(scope, context, args.get(index)) {
+            Ok(serde_json::Value::String(text)) => text,
+            Ok(value) => value.to_string(),
+            Err(_) => "<unserializable>".to_string(),
+        };
+        parts.push(part);
+    }
+    parts.join(" ")
 }
 
-impl Tasks {
-    fn install(&self, isolate: &mut OwnedIsolate, context: Local<Context>) {
-        let mut hs = v8::HandleScope::new(isolate);
-        let scope = hs.enter();
+fn console_log(
+    scope: v8::FunctionCallbackScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let context = scope.get_current_context().unwrap();
+    println!("{}", format_arguments(scope, context, &args));
+}
+
+fn console_error(
+    scope: v8::FunctionCallbackScope,
+    args: v8::FunctionCallbackArguments,
+    _rv: v8::ReturnValue,
+) {
+    let context = scope.get_current_context().unwrap();
+    eprintln!("{}", format_arguments(scope, context, &args));
+}
+
+/// A synchronous host `fetch` stub. It echoes the requested URL back as a
+/// response-shaped object; real network I/O would be driven through the
+/// surrounding tokio runtime and surfaced here as a resolved value.
+fn host_fetch(
+    scope: v8::FunctionCallbackScope,
+    args: v8::FunctionCallbackArguments,
+    mut rv: v8::ReturnValue,
+) {
+    let context = scope.get_current_context().unwrap();
+
+    let url = match decode(scope, context, args.get(0)) {
+        Ok(serde_json::Value::String(url)) => url,
+        _ => String::new(),
+    };
+
+    let response = serde_json::json!({
+        "url": url,
+        "ok": true,
+        "status": 200,
+    });
 
-        let mut cs = v8::ContextScope::new(scope, context);
-        let scope = cs.enter();
+    if let Ok(value) = encode(scope, context, &response) {
+        rv.set(value);
     }
 }